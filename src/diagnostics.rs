@@ -0,0 +1,102 @@
+use std::fmt;
+
+use super::lexer::{LexError, Position};
+use super::parser::ParseError;
+
+/// Any error that can stop compilation, together with where it happened.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompileError {
+    Lex(LexError, Position),
+    Parse(ParseError),
+}
+
+impl CompileError {
+    fn pos(&self) -> Position {
+        match self {
+            CompileError::Lex(_, pos) => *pos,
+            CompileError::Parse(err) => err.pos,
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompileError::Lex(err, pos) => write!(f, "{} at {}", err, pos),
+            CompileError::Parse(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<ParseError> for CompileError {
+    fn from(err: ParseError) -> Self {
+        CompileError::Parse(err)
+    }
+}
+
+/// Renders a `CompileError` against the original source, in the style of a
+/// compiler diagnostic: the offending line, a caret under the column, and
+/// the error message.
+pub fn render_error(input: &str, err: &CompileError) -> String {
+    let pos = err.pos();
+    let line = input.lines().nth(pos.line.saturating_sub(1)).unwrap_or("");
+    let gutter = format!("{}", pos.line);
+    let caret = format!("{}^", " ".repeat(pos.column.saturating_sub(1)));
+
+    format!(
+        "error: {}\n{pad} |\n{gutter} | {line}\n{pad} | {caret}",
+        err,
+        pad = " ".repeat(gutter.len()),
+        gutter = gutter,
+        line = line,
+        caret = caret,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ParseErrorType;
+
+    #[test]
+    fn renders_lex_error_on_its_source_line() {
+        let input = "let x = \"bad \\q escape\"";
+        let err = CompileError::Lex(
+            LexError::MalformedEscape("\\q".to_string()),
+            Position { line: 1, column: 15 },
+        );
+
+        assert_eq!(
+            render_error(input, &err),
+            "error: malformed escape sequence '\\q' at 1:15\n  |\n1 | let x = \"bad \\q escape\"\n  |               ^"
+        );
+    }
+
+    #[test]
+    fn renders_parse_error_with_caret_under_the_column() {
+        let input = "let x = )";
+        let err = CompileError::Parse(ParseError {
+            ty: ParseErrorType::ExpectedExpr,
+            pos: Position { line: 1, column: 9 },
+        });
+
+        assert_eq!(
+            render_error(input, &err),
+            "error: expected an expression at 1:9\n  |\n1 | let x = )\n  |         ^"
+        );
+    }
+
+    #[test]
+    fn renders_second_line_correctly() {
+        let input = "let a = 1\nlet b = )";
+        let err = CompileError::Parse(ParseError {
+            ty: ParseErrorType::ExpectedExpr,
+            pos: Position { line: 2, column: 9 },
+        });
+
+        assert_eq!(
+            render_error(input, &err),
+            "error: expected an expression at 2:9\n  |\n2 | let b = )\n  |         ^"
+        );
+    }
+}
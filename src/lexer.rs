@@ -1,3 +1,4 @@
+use std::error::Error;
 use std::fmt;
 
 #[derive(Debug, PartialEq, Clone)]
@@ -27,6 +28,7 @@ pub enum Token {
     RightParen,
     Comma,
     Underscore,
+    Pipe,
 
     // Служебные
     EOI,
@@ -38,6 +40,41 @@ impl fmt::Display for Token {
     }
 }
 
+/// A 1-based line/column location within the original source text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Errors produced while scanning raw source text into tokens.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexError {
+    UnexpectedChar(char),
+    UnterminatedString,
+    MalformedNumber(String),
+    MalformedEscape(String),
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LexError::UnexpectedChar(c) => write!(f, "unexpected character '{}'", c),
+            LexError::UnterminatedString => write!(f, "unterminated string literal"),
+            LexError::MalformedNumber(s) => write!(f, "malformed number '{}'", s),
+            LexError::MalformedEscape(s) => write!(f, "malformed escape sequence '{}'", s),
+        }
+    }
+}
+
+impl Error for LexError {}
+
 #[derive(Clone)]
 pub struct Lexer {
     input: Vec<char>,
@@ -56,63 +93,79 @@ impl Lexer {
         }
     }
 
-    pub fn next_token(&mut self) -> Token {
+    pub fn next_token(&mut self) -> Result<(Token, Position), (LexError, Position)> {
         self.skip_whitespace();
+        let pos = self.current_pos();
+        self.scan_token().map(|token| (token, pos))
+    }
 
+    /// Scans a single token, attaching to any error the exact position of
+    /// the offending character rather than the position the token started
+    /// at (the two can diverge deep inside a string literal).
+    fn scan_token(&mut self) -> Result<Token, (LexError, Position)> {
         if self.position >= self.input.len() {
-            return Token::EOI;
+            return Ok(Token::EOI);
         }
 
+        let pos = self.current_pos();
         let c = self.input[self.position];
         match c {
-            '0'..='9' => self.read_number(),
-            'a'..='z' | 'A'..='Z' => self.read_word(),
+            '0'..='9' => self.read_number().map_err(|e| (e, pos)),
+            'a'..='z' | 'A'..='Z' => Ok(self.read_word()),
             '=' => {
                 self.advance();
-                Token::Equal
+                Ok(Token::Equal)
             }
             ':' => {
                 self.advance();
-                Token::Colon
+                Ok(Token::Colon)
             }
             '-' if self.peek() == '>' => {
                 self.advance();
                 self.advance();
-                Token::Arrow
+                Ok(Token::Arrow)
             }
             '{' => {
                 self.advance();
-                Token::LeftCurly
+                Ok(Token::LeftCurly)
             }
             '}' => {
                 self.advance();
-                Token::RightCurly
+                Ok(Token::RightCurly)
             }
             '(' => {
                 self.advance();
-                Token::LeftParen
+                Ok(Token::LeftParen)
             }
             ')' => {
                 self.advance();
-                Token::RightParen
+                Ok(Token::RightParen)
             }
             ',' => {
                 self.advance();
-                Token::Comma
+                Ok(Token::Comma)
             }
             '_' => {
                 self.advance();
-                Token::Underscore
+                Ok(Token::Underscore)
+            }
+            '|' => {
+                self.advance();
+                Ok(Token::Pipe)
             }
             '!' => {
                 self.advance();
-                panic!("Unexpected character '!' at {}:{}", self.line, self.column)
+                Err((LexError::UnexpectedChar('!'), pos))
             }
             '"' => self.read_string(),
-            _ => panic!(
-                "Unexpected character '{}' at {}:{}",
-                c, self.line, self.column
-            ),
+            _ => Err((LexError::UnexpectedChar(c), pos)),
+        }
+    }
+
+    fn current_pos(&self) -> Position {
+        Position {
+            line: self.line,
+            column: self.column,
         }
     }
 
@@ -134,7 +187,7 @@ impl Lexer {
         }
     }
 
-    fn read_number(&mut self) -> Token {
+    fn read_number(&mut self) -> Result<Token, LexError> {
         let start = self.position;
         while self.position < self.input.len() && self.input[self.position].is_ascii_digit() {
             self.advance();
@@ -146,10 +199,14 @@ impl Lexer {
                 self.advance();
             }
             let s: String = self.input[start..self.position].iter().collect();
-            Token::FloatLiteral(s.parse().unwrap())
+            s.parse()
+                .map(Token::FloatLiteral)
+                .map_err(|_| LexError::MalformedNumber(s))
         } else {
             let s: String = self.input[start..self.position].iter().collect();
-            Token::IntegerLiteral(s.parse().unwrap())
+            s.parse()
+                .map(Token::IntegerLiteral)
+                .map_err(|_| LexError::MalformedNumber(s))
         }
     }
 
@@ -184,17 +241,93 @@ impl Lexer {
         }
     }
 
-    fn read_string(&mut self) -> Token {
+    fn read_string(&mut self) -> Result<Token, (LexError, Position)> {
+        let start = self.current_pos();
         self.advance(); // Пропускаем открывающую кавычку
+        let mut result = String::new();
+
+        loop {
+            if self.position >= self.input.len() {
+                return Err((LexError::UnterminatedString, start));
+            }
+
+            match self.input[self.position] {
+                '"' => {
+                    self.advance();
+                    break;
+                }
+                '\\' => {
+                    let escape_pos = self.current_pos();
+                    self.advance();
+                    result.push(self.read_escape(escape_pos)?);
+                }
+                c => {
+                    result.push(c);
+                    self.advance();
+                }
+            }
+        }
+
+        Ok(Token::StringLiteral(result))
+    }
+
+    /// Reads the escape following a `\` at `escape_pos`, returning that
+    /// position on error so diagnostics point at the escape, not the
+    /// opening quote of the string it's in.
+    fn read_escape(&mut self, escape_pos: Position) -> Result<char, (LexError, Position)> {
+        if self.position >= self.input.len() {
+            return Err((LexError::UnterminatedString, escape_pos));
+        }
+
+        let escaped = self.input[self.position];
+        match escaped {
+            'n' => {
+                self.advance();
+                Ok('\n')
+            }
+            't' => {
+                self.advance();
+                Ok('\t')
+            }
+            'r' => {
+                self.advance();
+                Ok('\r')
+            }
+            '\\' => {
+                self.advance();
+                Ok('\\')
+            }
+            '"' => {
+                self.advance();
+                Ok('"')
+            }
+            'u' => self.read_unicode_escape(escape_pos),
+            other => Err((LexError::MalformedEscape(format!("\\{}", other)), escape_pos)),
+        }
+    }
+
+    fn read_unicode_escape(&mut self, escape_pos: Position) -> Result<char, (LexError, Position)> {
+        self.advance(); // Пропускаем 'u'
+
+        if self.position >= self.input.len() || self.input[self.position] != '{' {
+            return Err((LexError::MalformedEscape("\\u".to_string()), escape_pos));
+        }
+        self.advance(); // Пропускаем '{'
+
         let start = self.position;
-        while self.position < self.input.len() && self.input[self.position] != '"' {
+        while self.position < self.input.len() && self.input[self.position] != '}' {
             self.advance();
         }
-        let s: String = self.input[start..self.position].iter().collect();
-        if self.position < self.input.len() {
-            self.advance(); // Пропускаем закрывающую кавычку
+        if self.position >= self.input.len() {
+            return Err((LexError::UnterminatedString, escape_pos));
         }
-        Token::StringLiteral(s)
+        let hex: String = self.input[start..self.position].iter().collect();
+        self.advance(); // Пропускаем '}'
+
+        let code = u32::from_str_radix(&hex, 16)
+            .map_err(|_| (LexError::MalformedEscape(format!("\\u{{{}}}", hex)), escape_pos))?;
+        char::from_u32(code)
+            .ok_or_else(|| (LexError::MalformedEscape(format!("\\u{{{}}}", hex)), escape_pos))
     }
 
     fn skip_whitespace(&mut self) {
@@ -212,3 +345,62 @@ impl Lexer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex_one(input: &str) -> Result<Token, (LexError, Position)> {
+        Lexer::new(input).next_token().map(|(token, _)| token)
+    }
+
+    #[test]
+    fn reads_basic_escapes() {
+        assert_eq!(
+            lex_one(r#""a\nb\tc\rd\\e\"f""#),
+            Ok(Token::StringLiteral("a\nb\tc\rd\\e\"f".to_string()))
+        );
+    }
+
+    #[test]
+    fn reads_unicode_escape() {
+        assert_eq!(
+            lex_one(r#""\u{48}\u{49}""#),
+            Ok(Token::StringLiteral("HI".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_escape() {
+        assert_eq!(
+            lex_one(r#""bad \q escape""#),
+            Err((LexError::MalformedEscape("\\q".to_string()), Position { line: 1, column: 6 }))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_unicode_escape() {
+        assert_eq!(
+            lex_one(r#""\u{zzzz}""#),
+            Err((
+                LexError::MalformedEscape("\\u{zzzz}".to_string()),
+                Position { line: 1, column: 2 }
+            ))
+        );
+    }
+
+    #[test]
+    fn rejects_unterminated_string() {
+        assert_eq!(
+            lex_one("\"unterminated"),
+            Err((LexError::UnterminatedString, Position { line: 1, column: 1 }))
+        );
+    }
+
+    #[test]
+    fn escape_error_position_points_at_backslash_not_string_start() {
+        let (err, pos) = lex_one(r#""hello world this is fine \q bad""#).unwrap_err();
+        assert_eq!(err, LexError::MalformedEscape("\\q".to_string()));
+        assert_eq!(pos, Position { line: 1, column: 27 });
+    }
+}
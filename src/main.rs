@@ -1,25 +1,103 @@
+mod diagnostics;
 mod lexer;
 mod parser;
 
-use lexer::Lexer;
-use parser::Parser;
+use std::env;
+use std::fs;
+use std::process;
+
+use diagnostics::{render_error, CompileError};
+use lexer::{Lexer, Token};
+use parser::{Item, Parser};
+
+enum Emit {
+    Tokens,
+    Ast,
+    Check,
+}
 
 fn main() {
-    let input = "
-        let baz = a: Int -> a
+    let mut path = None;
+    let mut emit = Emit::Ast;
 
-        let complex = {
-            let helper = a: Int -> a
-            helper(9)
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--emit" => {
+                let mode = args.next().unwrap_or_else(|| {
+                    eprintln!("error: --emit requires an argument (tokens, ast or check)");
+                    process::exit(1);
+                });
+                emit = match mode.as_str() {
+                    "tokens" => Emit::Tokens,
+                    "ast" => Emit::Ast,
+                    "check" => Emit::Check,
+                    other => {
+                        eprintln!("error: unknown --emit mode '{}'", other);
+                        process::exit(1);
+                    }
+                };
+            }
+            _ => path = Some(arg),
         }
-    ";
+    }
+
+    let path = path.unwrap_or_else(|| {
+        eprintln!("usage: hroma <file> [--emit tokens|ast|check]");
+        process::exit(1);
+    });
+
+    let input = fs::read_to_string(&path).unwrap_or_else(|err| {
+        eprintln!("error: could not read '{}': {}", path, err);
+        process::exit(1);
+    });
+
+    match emit {
+        Emit::Tokens => emit_tokens(&input),
+        Emit::Ast => emit_ast(&input),
+        Emit::Check => emit_check(&input),
+    }
+}
 
-    // Лексический анализ
+/// Lexes and parses `input`, returning the top-level items or the first
+/// diagnostic encountered.
+pub fn compile(input: &str) -> Result<Vec<Item>, CompileError> {
     let lexer = Lexer::new(input);
+    let mut parser = Parser::new(lexer)?;
+    Ok(parser.parse()?)
+}
+
+fn emit_tokens(input: &str) {
+    let mut lexer = Lexer::new(input);
+    loop {
+        match lexer.next_token() {
+            Ok((token, pos)) => {
+                println!("{}:{} {}", pos.line, pos.column, token);
+                if token == Token::EOI {
+                    break;
+                }
+            }
+            Err((err, pos)) => {
+                eprintln!("{}", render_error(input, &CompileError::Lex(err, pos)));
+                process::exit(1);
+            }
+        }
+    }
+}
 
-    // Синтаксический анализ
-    let mut parser = Parser::new(lexer);
-    let ast = parser.parse();
+fn emit_ast(input: &str) {
+    match compile(input) {
+        Ok(items) => println!("{:#?}", items),
+        Err(err) => {
+            eprintln!("{}", render_error(input, &err));
+            process::exit(1);
+        }
+    }
+}
 
-    println!("AST: {:#?}", ast);
+fn emit_check(input: &str) {
+    if let Err(err) = compile(input) {
+        eprintln!("{}", render_error(input, &err));
+        process::exit(1);
+    }
 }
@@ -1,10 +1,25 @@
-use super::lexer::{Lexer, Token};
+use super::lexer::{LexError, Lexer, Position, Token};
+use std::error::Error;
+use std::fmt;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Type {
     Int,
     Float,
     String,
+    Named(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Item {
+    Function(Function),
+    TypeDecl(TypeDecl),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TypeDecl {
+    pub name: String,
+    pub variants: Vec<(String, Vec<Type>)>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -29,43 +44,165 @@ pub enum Expr {
     FloatLiteral(f64),
     StringLiteral(String),
     Call(String, Vec<Expr>),
+    Match {
+        scrutinee: Box<Expr>,
+        arms: Vec<(Pattern, Expr)>,
+        default: Option<Box<Expr>>,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Pattern {
+    IntLiteral(i32),
+    FloatLiteral(f64),
+    StringLiteral(String),
+    Wildcard,
+}
+
+/// The kind of syntax error encountered, without its location.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorType {
+    MissingRParen,
+    MissingRCurly,
+    UnexpectedToken { expected: Token, got: Token },
+    ExpectedType,
+    ExpectedIdent,
+    ExpectedTypeIdent,
+    ExpectedExpr,
+    ExpectedPattern,
+    Lex(LexError),
+}
+
+impl fmt::Display for ParseErrorType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseErrorType::MissingRParen => write!(f, "expected ')'"),
+            ParseErrorType::MissingRCurly => write!(f, "expected '}}'"),
+            ParseErrorType::UnexpectedToken { expected, got } => {
+                write!(f, "expected {:?}, got {:?}", expected, got)
+            }
+            ParseErrorType::ExpectedType => write!(f, "expected a type (Int, Float, String or a type name)"),
+            ParseErrorType::ExpectedIdent => write!(f, "expected an identifier"),
+            ParseErrorType::ExpectedTypeIdent => {
+                write!(f, "expected a capitalized type name")
+            }
+            ParseErrorType::ExpectedExpr => write!(f, "expected an expression"),
+            ParseErrorType::ExpectedPattern => {
+                write!(f, "expected a pattern (literal or '_')")
+            }
+            ParseErrorType::Lex(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// A syntax error together with the position it was raised at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub ty: ParseErrorType,
+    pub pos: Position,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}", self.ty, self.pos)
+    }
 }
 
+impl Error for ParseError {}
+
 pub struct Parser {
     lexer: Lexer,
-    current_token: Token,
-    peek_token: Option<Token>,
+    current: (Token, Position),
+    peek: Option<(Token, Position)>,
 }
 
 impl Parser {
-    pub fn new(mut lexer: Lexer) -> Self {
-        let current_token = lexer.next_token();
-        let peek_token = Some(lexer.next_token());
-        Parser {
+    pub fn new(mut lexer: Lexer) -> Result<Self, ParseError> {
+        let current = lexer
+            .next_token()
+            .map_err(|(e, pos)| ParseError {
+                pos,
+                ty: ParseErrorType::Lex(e),
+            })?;
+        let peek = Some(lexer.next_token().map_err(|(e, pos)| ParseError {
+            pos,
+            ty: ParseErrorType::Lex(e),
+        })?);
+        Ok(Parser {
             lexer,
-            current_token,
-            peek_token,
+            current,
+            peek,
+        })
+    }
+
+    pub fn parse(&mut self) -> Result<Vec<Item>, ParseError> {
+        let mut items = Vec::new();
+        while self.current.0 != Token::EOI {
+            if matches!(self.current.0, Token::KeywordType) {
+                items.push(Item::TypeDecl(self.parse_type_decl()?));
+            } else {
+                items.push(Item::Function(self.parse_function()?));
+            }
+        }
+        Ok(items)
+    }
+
+    fn parse_type_decl(&mut self) -> Result<TypeDecl, ParseError> {
+        self.advance()?; // `type`
+        let name = self.parse_type_ident()?;
+        self.expect(Token::Equal)?;
+
+        let mut variants = Vec::new();
+        loop {
+            let variant_name = self.parse_type_ident()?;
+            let mut payload = Vec::new();
+
+            if self.current.0 == Token::LeftParen {
+                self.advance()?;
+                while self.current.0 != Token::RightParen {
+                    payload.push(self.parse_type()?);
+                    if self.current.0 == Token::Comma {
+                        self.advance()?;
+                    }
+                }
+                self.expect(Token::RightParen)?;
+            }
+
+            variants.push((variant_name, payload));
+
+            if self.current.0 == Token::Pipe {
+                self.advance()?;
+                continue;
+            }
+            break;
         }
+
+        Ok(TypeDecl { name, variants })
     }
 
-    pub fn parse(&mut self) -> Vec<Function> {
-        let mut functions = Vec::new();
-        while self.current_token != Token::EOI {
-            functions.push(self.parse_function());
+    fn parse_type_ident(&mut self) -> Result<String, ParseError> {
+        if let Token::TypeIdent(name) = &self.current.0 {
+            let name = name.clone();
+            self.advance()?;
+            Ok(name)
+        } else {
+            Err(ParseError {
+                ty: ParseErrorType::ExpectedTypeIdent,
+                pos: self.current.1,
+            })
         }
-        functions
     }
 
-    fn parse_function(&mut self) -> Function {
-        let is_nonlin = matches!(self.current_token, Token::KeywordLetBang);
-        self.advance();
+    fn parse_function(&mut self) -> Result<Function, ParseError> {
+        let is_nonlin = matches!(self.current.0, Token::KeywordLetBang);
+        self.advance()?;
 
-        let name = self.parse_ident();
-        self.expect(Token::Equal);
+        let name = self.parse_ident()?;
+        self.expect(Token::Equal)?;
 
-        let (params, body, return_expr) = self.parse_function_body();
+        let (params, body, return_expr) = self.parse_function_body()?;
 
-        if is_nonlin {
+        Ok(if is_nonlin {
             Function::NonlinFunc {
                 name,
                 params,
@@ -79,201 +216,421 @@ impl Parser {
                 body,
                 return_expr,
             }
-        }
+        })
     }
 
-    fn parse_function_body(&mut self) -> (Vec<(String, Type)>, Vec<Function>, Vec<Expr>) {
+    #[allow(clippy::type_complexity)]
+    fn parse_function_body(
+        &mut self,
+    ) -> Result<(Vec<(String, Type)>, Vec<Function>, Vec<Expr>), ParseError> {
         // Проверяем синтаксис лямбды: ident: type -> ...
-        if let Token::Ident(_) = &self.current_token {
-            if let Some(Token::Colon) = &self.peek_token {
+        if let Token::Ident(_) = &self.current.0 {
+            if let Some((Token::Colon, _)) = &self.peek {
                 return self.parse_lambda_style();
             }
         }
 
-        match &self.current_token {
+        match &self.current.0 {
             Token::IntegerLiteral(_) => {
                 let n = if let Token::IntegerLiteral(n) =
-                    std::mem::replace(&mut self.current_token, Token::EOI)
+                    std::mem::replace(&mut self.current.0, Token::EOI)
                 {
                     n
                 } else {
                     unreachable!()
                 };
-                self.advance();
-                (vec![], vec![], vec![Expr::IntLiteral(n)])
+                self.advance()?;
+                Ok((vec![], vec![], vec![Expr::IntLiteral(n)]))
             }
             Token::FloatLiteral(_) => {
                 let f = if let Token::FloatLiteral(f) =
-                    std::mem::replace(&mut self.current_token, Token::EOI)
+                    std::mem::replace(&mut self.current.0, Token::EOI)
                 {
                     f
                 } else {
                     unreachable!()
                 };
-                self.advance();
-                (vec![], vec![], vec![Expr::FloatLiteral(f)])
+                self.advance()?;
+                Ok((vec![], vec![], vec![Expr::FloatLiteral(f)]))
             }
             Token::StringLiteral(_) => {
                 let s = if let Token::StringLiteral(s) =
-                    std::mem::replace(&mut self.current_token, Token::EOI)
+                    std::mem::replace(&mut self.current.0, Token::EOI)
                 {
                     s
                 } else {
                     unreachable!()
                 };
-                self.advance();
-                (vec![], vec![], vec![Expr::StringLiteral(s)])
+                self.advance()?;
+                Ok((vec![], vec![], vec![Expr::StringLiteral(s)]))
             }
             Token::LeftCurly => {
-                self.expect(Token::LeftCurly);
+                self.expect(Token::LeftCurly)?;
                 let params = Vec::new();
                 let mut body = Vec::new();
                 let mut return_expr = Vec::new();
 
-                while !matches!(&self.current_token, Token::RightCurly | Token::EOI) {
+                while !matches!(&self.current.0, Token::RightCurly | Token::EOI) {
                     if matches!(
-                        &self.current_token,
+                        &self.current.0,
                         Token::KeywordLet | Token::KeywordLetBang
                     ) {
-                        body.push(self.parse_function());
+                        body.push(self.parse_function()?);
                     } else {
-                        return_expr.push(self.parse_expr());
+                        return_expr.push(self.parse_expr()?);
                     }
                 }
 
-                self.expect(Token::RightCurly);
-                (params, body, return_expr)
+                self.expect(Token::RightCurly)?;
+                Ok((params, body, return_expr))
             }
             _ => {
-                let expr = self.parse_expr();
-                (vec![], vec![], vec![expr])
+                let expr = self.parse_expr()?;
+                Ok((vec![], vec![], vec![expr]))
             }
         }
     }
 
-    fn parse_lambda_style(&mut self) -> (Vec<(String, Type)>, Vec<Function>, Vec<Expr>) {
+    #[allow(clippy::type_complexity)]
+    fn parse_lambda_style(
+        &mut self,
+    ) -> Result<(Vec<(String, Type)>, Vec<Function>, Vec<Expr>), ParseError> {
         let mut params = Vec::new();
 
         // Парсим параметры в формате a: Int, b: Float
         loop {
-            let name = self.parse_ident();
-            self.expect(Token::Colon);
-            let typ = self.parse_type();
+            let name = self.parse_ident()?;
+            self.expect(Token::Colon)?;
+            let typ = self.parse_type()?;
             params.push((name, typ));
 
-            match self.current_token {
+            match self.current.0 {
                 Token::Comma => {
-                    self.advance();
+                    self.advance()?;
                     continue;
                 }
                 Token::Arrow => break,
-                _ => panic!("Expected ',' or '->' after parameter"),
+                _ => {
+                    return Err(ParseError {
+                        ty: ParseErrorType::UnexpectedToken {
+                            expected: Token::Arrow,
+                            got: self.current.0.clone(),
+                        },
+                        pos: self.current.1,
+                    })
+                }
             }
         }
 
-        self.expect(Token::Arrow);
-        let return_expr = vec![self.parse_expr()];
+        self.expect(Token::Arrow)?;
+        let return_expr = vec![self.parse_expr()?];
 
-        (params, vec![], return_expr)
+        Ok((params, vec![], return_expr))
     }
 
-    fn parse_type(&mut self) -> Type {
-        match &self.current_token {
-            Token::TypeIdent(name) if name == "Int" => {
-                self.advance();
-                Type::Int
+    fn parse_type(&mut self) -> Result<Type, ParseError> {
+        match &self.current.0 {
+            Token::TypeIdent(name) => {
+                let typ = match name.as_str() {
+                    "Int" => Type::Int,
+                    "Float" => Type::Float,
+                    "String" => Type::String,
+                    _ => Type::Named(name.clone()),
+                };
+                self.advance()?;
+                Ok(typ)
+            }
+            _ => Err(ParseError {
+                ty: ParseErrorType::ExpectedType,
+                pos: self.current.1,
+            }),
+        }
+    }
+
+    fn parse_pattern(&mut self) -> Result<Pattern, ParseError> {
+        match &self.current.0 {
+            Token::Underscore => {
+                self.advance()?;
+                Ok(Pattern::Wildcard)
+            }
+            Token::IntegerLiteral(_) => {
+                let n = if let Token::IntegerLiteral(n) =
+                    std::mem::replace(&mut self.current.0, Token::EOI)
+                {
+                    n
+                } else {
+                    unreachable!()
+                };
+                self.advance()?;
+                Ok(Pattern::IntLiteral(n))
             }
-            Token::TypeIdent(name) if name == "Float" => {
-                self.advance();
-                Type::Float
+            Token::FloatLiteral(_) => {
+                let f = if let Token::FloatLiteral(f) =
+                    std::mem::replace(&mut self.current.0, Token::EOI)
+                {
+                    f
+                } else {
+                    unreachable!()
+                };
+                self.advance()?;
+                Ok(Pattern::FloatLiteral(f))
             }
-            Token::TypeIdent(name) if name == "String" => {
-                self.advance();
-                Type::String
+            Token::StringLiteral(_) => {
+                let s = if let Token::StringLiteral(s) =
+                    std::mem::replace(&mut self.current.0, Token::EOI)
+                {
+                    s
+                } else {
+                    unreachable!()
+                };
+                self.advance()?;
+                Ok(Pattern::StringLiteral(s))
             }
-            _ => panic!("Expected type (Int, Float or String)"),
+            _ => Err(ParseError {
+                ty: ParseErrorType::ExpectedPattern,
+                pos: self.current.1,
+            }),
         }
     }
 
-    fn parse_expr(&mut self) -> Expr {
-        match &self.current_token {
+    fn parse_expr(&mut self) -> Result<Expr, ParseError> {
+        match &self.current.0 {
+            Token::KeywordMatch => {
+                self.advance()?;
+                let scrutinee = Box::new(self.parse_expr()?);
+                self.expect(Token::KeywordOf)?;
+
+                let mut arms = Vec::new();
+                let mut default = None;
+                loop {
+                    if matches!(self.current.0, Token::KeywordDefault) {
+                        self.advance()?;
+                        self.expect(Token::Arrow)?;
+                        default = Some(Box::new(self.parse_expr()?));
+                    } else {
+                        let pattern = self.parse_pattern()?;
+                        self.expect(Token::Arrow)?;
+                        let expr = self.parse_expr()?;
+                        arms.push((pattern, expr));
+                    }
+
+                    if self.current.0 == Token::Comma {
+                        self.advance()?;
+                    } else {
+                        break;
+                    }
+                }
+
+                Ok(Expr::Match {
+                    scrutinee,
+                    arms,
+                    default,
+                })
+            }
             Token::Ident(name) => {
                 let name = name.clone();
-                self.advance();
+                self.advance()?;
 
-                if self.current_token == Token::LeftParen {
-                    self.advance();
+                if self.current.0 == Token::LeftParen {
+                    self.advance()?;
                     let mut args = Vec::new();
 
-                    while self.current_token != Token::RightParen {
-                        args.push(self.parse_expr());
-                        if self.current_token == Token::Comma {
-                            self.advance();
+                    while self.current.0 != Token::RightParen {
+                        args.push(self.parse_expr()?);
+                        if self.current.0 == Token::Comma {
+                            self.advance()?;
                         }
                     }
 
-                    self.expect(Token::RightParen);
-                    Expr::Call(name, args)
+                    self.expect(Token::RightParen)?;
+                    Ok(Expr::Call(name, args))
                 } else {
-                    Expr::Call(name, vec![])
+                    Ok(Expr::Call(name, vec![]))
                 }
             }
             Token::IntegerLiteral(_) => {
                 let n = if let Token::IntegerLiteral(n) =
-                    std::mem::replace(&mut self.current_token, Token::EOI)
+                    std::mem::replace(&mut self.current.0, Token::EOI)
                 {
                     n
                 } else {
                     unreachable!()
                 };
-                self.advance();
-                Expr::IntLiteral(n)
+                self.advance()?;
+                Ok(Expr::IntLiteral(n))
             }
             Token::FloatLiteral(_) => {
                 let f = if let Token::FloatLiteral(f) =
-                    std::mem::replace(&mut self.current_token, Token::EOI)
+                    std::mem::replace(&mut self.current.0, Token::EOI)
                 {
                     f
                 } else {
                     unreachable!()
                 };
-                self.advance();
-                Expr::FloatLiteral(f)
+                self.advance()?;
+                Ok(Expr::FloatLiteral(f))
             }
             Token::StringLiteral(_) => {
                 let s = if let Token::StringLiteral(s) =
-                    std::mem::replace(&mut self.current_token, Token::EOI)
+                    std::mem::replace(&mut self.current.0, Token::EOI)
                 {
                     s
                 } else {
                     unreachable!()
                 };
-                self.advance();
-                Expr::StringLiteral(s)
+                self.advance()?;
+                Ok(Expr::StringLiteral(s))
             }
-            _ => panic!("Unexpected token in expression: {:?}", self.current_token),
+            _ => Err(ParseError {
+                ty: ParseErrorType::ExpectedExpr,
+                pos: self.current.1,
+            }),
         }
     }
 
-    fn advance(&mut self) {
-        self.current_token = self.peek_token.take().unwrap_or(Token::EOI);
-        self.peek_token = Some(self.lexer.next_token());
+    fn advance(&mut self) -> Result<(), ParseError> {
+        let (token, pos) = self.peek.take().unwrap_or((Token::EOI, self.current.1));
+        self.current = (token, pos);
+        self.peek = Some(self.lexer.next_token().map_err(|(e, pos)| ParseError {
+            pos,
+            ty: ParseErrorType::Lex(e),
+        })?);
+        Ok(())
     }
 
-    fn expect(&mut self, expected: Token) {
-        if self.current_token != expected {
-            panic!("Expected {:?}, got {:?}", expected, self.current_token);
+    fn expect(&mut self, expected: Token) -> Result<(), ParseError> {
+        if self.current.0 != expected {
+            let ty = match expected {
+                Token::RightParen => ParseErrorType::MissingRParen,
+                Token::RightCurly => ParseErrorType::MissingRCurly,
+                _ => ParseErrorType::UnexpectedToken {
+                    expected,
+                    got: self.current.0.clone(),
+                },
+            };
+            return Err(ParseError {
+                ty,
+                pos: self.current.1,
+            });
         }
-        self.advance();
+        self.advance()
     }
 
-    fn parse_ident(&mut self) -> String {
-        if let Token::Ident(name) = &self.current_token {
+    fn parse_ident(&mut self) -> Result<String, ParseError> {
+        if let Token::Ident(name) = &self.current.0 {
             let name = name.clone();
-            self.advance();
-            name
+            self.advance()?;
+            Ok(name)
         } else {
-            panic!("Expected identifier");
+            Err(ParseError {
+                ty: ParseErrorType::ExpectedIdent,
+                pos: self.current.1,
+            })
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_ok(input: &str) -> Vec<Item> {
+        let mut parser = Parser::new(Lexer::new(input)).expect("lexing should succeed");
+        parser.parse().expect("parsing should succeed")
+    }
+
+    fn parse_err(input: &str) -> ParseError {
+        match Parser::new(Lexer::new(input)) {
+            Ok(mut parser) => parser.parse().expect_err("expected a parse error"),
+            Err(err) => err,
+        }
+    }
+
+    fn only_return_expr(items: &[Item]) -> &Expr {
+        match &items[..] {
+            [Item::Function(Function::LinFunc { return_expr, .. })] => &return_expr[0],
+            other => panic!("expected a single function, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_multi_arm_match_with_default() {
+        let items = parse_ok(
+            r#"let f = match x of 0 -> "zero", 1 -> "one", default -> "many""#,
+        );
+
+        assert_eq!(
+            only_return_expr(&items),
+            &Expr::Match {
+                scrutinee: Box::new(Expr::Call("x".to_string(), vec![])),
+                arms: vec![
+                    (Pattern::IntLiteral(0), Expr::StringLiteral("zero".to_string())),
+                    (Pattern::IntLiteral(1), Expr::StringLiteral("one".to_string())),
+                ],
+                default: Some(Box::new(Expr::StringLiteral("many".to_string()))),
+            }
+        );
+    }
+
+    #[test]
+    fn match_without_trailing_comma_terminates_at_eoi() {
+        let items = parse_ok("let f = match x of default -> 1");
+
+        assert_eq!(
+            only_return_expr(&items),
+            &Expr::Match {
+                scrutinee: Box::new(Expr::Call("x".to_string(), vec![])),
+                arms: vec![],
+                default: Some(Box::new(Expr::IntLiteral(1))),
+            }
+        );
+    }
+
+    #[test]
+    fn match_with_invalid_pattern_reports_expected_pattern() {
+        let err = parse_err("let f = match x of foo -> 1");
+        assert_eq!(err.ty, ParseErrorType::ExpectedPattern);
+    }
+
+    #[test]
+    fn parses_multi_variant_type_decl_with_mixed_payload_arities() {
+        let items = parse_ok("type Shape = Point | Circle(Float) | Rect(Float, Float)");
+
+        assert_eq!(
+            items,
+            vec![Item::TypeDecl(TypeDecl {
+                name: "Shape".to_string(),
+                variants: vec![
+                    ("Point".to_string(), vec![]),
+                    ("Circle".to_string(), vec![Type::Float]),
+                    ("Rect".to_string(), vec![Type::Float, Type::Float]),
+                ],
+            })]
+        );
+    }
+
+    #[test]
+    fn parses_variant_payload_referencing_a_named_type() {
+        let items = parse_ok("type Tree = Leaf | Node(Tree, Tree)");
+
+        assert_eq!(
+            items,
+            vec![Item::TypeDecl(TypeDecl {
+                name: "Tree".to_string(),
+                variants: vec![
+                    ("Leaf".to_string(), vec![]),
+                    (
+                        "Node".to_string(),
+                        vec![Type::Named("Tree".to_string()), Type::Named("Tree".to_string())]
+                    ),
+                ],
+            })]
+        );
+    }
+
+    #[test]
+    fn type_decl_with_lowercase_name_reports_expected_type_ident() {
+        let err = parse_err("type shape = Circle");
+        assert_eq!(err.ty, ParseErrorType::ExpectedTypeIdent);
+    }
+}